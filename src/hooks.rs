@@ -0,0 +1,50 @@
+use futures::{future, Future};
+use hyper::server::Service;
+use std::sync::Arc;
+
+/// Calls `inspect` with every error `inner` returns, then passes it through.
+pub struct ErrorInspector<S, F> {
+    pub inner: Arc<S>,
+    pub inspect: Arc<F>,
+}
+
+impl<S, F> Service for ErrorInspector<S, F>
+where
+    S: Service + 'static,
+    F: Fn(&S::Error) + 'static,
+{
+    type Request = S::Request;
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Box<Future<Item = S::Response, Error = S::Error>>;
+
+    fn call(&self, req: S::Request) -> Self::Future {
+        let inspect = self.inspect.clone();
+        Box::new(self.inner.call(req).map_err(move |e| {
+            inspect(&e);
+            e
+        }))
+    }
+}
+
+/// Replaces any error `inner` returns with a fixed fallback response.
+pub struct IgnoreErrors<S: Service> {
+    pub inner: Arc<S>,
+    pub fallback: S::Response,
+}
+
+impl<S> Service for IgnoreErrors<S>
+where
+    S: Service + 'static,
+    S::Response: Clone + 'static,
+{
+    type Request = S::Request;
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Box<Future<Item = S::Response, Error = S::Error>>;
+
+    fn call(&self, req: S::Request) -> Self::Future {
+        let fallback = self.fallback.clone();
+        Box::new(self.inner.call(req).or_else(move |_| future::ok(fallback)))
+    }
+}
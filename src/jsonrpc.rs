@@ -0,0 +1,361 @@
+use futures::{future, Future, Stream};
+use hyper;
+use hyper::header::{ContentLength, ContentType};
+use hyper::server::{Request, Response, Service};
+use hyper::StatusCode;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json;
+use serde_json::Value;
+use std::panic::AssertUnwindSafe;
+use std::sync::Arc;
+
+use server::{Error, ErrorLike};
+
+/// Speaks JSON-RPC 2.0. `method` is the name this server answers to; a
+/// top-level array is treated as a batch.
+pub struct JsonRpcServer<S> {
+    pub inner: Arc<S>,
+    pub method: String,
+}
+
+#[derive(Deserialize)]
+struct Envelope {
+    #[serde(default)]
+    jsonrpc: Option<String>,
+    #[serde(default)]
+    method: Option<String>,
+    #[serde(default)]
+    params: Value,
+    #[serde(default)]
+    id: Option<Value>,
+}
+
+fn is_valid_envelope(envelope: &Envelope) -> bool {
+    envelope.jsonrpc.as_ref().map(|v| v == "2.0").unwrap_or(false) && envelope.method.is_some()
+}
+
+fn rpc_error(code: i64, message: String, data: Option<Value>, id: Value) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "error": {
+            "code": code,
+            "message": message,
+            "data": data,
+        },
+        "id": id,
+    })
+}
+
+fn rpc_success(result: Value, id: Value) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "result": result,
+        "id": id,
+    })
+}
+
+fn json_response(body: Value) -> Response {
+    let body = body.to_string();
+    let len = body.len() as u64;
+    Response::new()
+        .with_body(body)
+        .with_header(ContentLength(len))
+        .with_header(ContentType::json())
+        .with_status(StatusCode::Ok)
+}
+
+fn empty_response() -> Response {
+    Response::new().with_status(StatusCode::NoContent)
+}
+
+/// Dispatches a single already-parsed JSON-RPC call. Returns `None` for
+/// notifications (no array entry / no body), `Some(entry)` otherwise.
+fn dispatch_one<S>(
+    service: Arc<S>,
+    method_name: String,
+    value: Value,
+) -> Box<Future<Item = Option<Value>, Error = hyper::Error>>
+where
+    S: Service + 'static,
+    S::Request: DeserializeOwned + 'static,
+    S::Response: Serialize,
+    S::Error: ErrorLike,
+{
+    let envelope: Envelope = match serde_json::from_value(value) {
+        Ok(e) => e,
+        Err(e) => {
+            return Box::new(future::ok(Some(rpc_error(
+                -32600,
+                e.to_string(),
+                None,
+                Value::Null,
+            ))));
+        }
+    };
+
+    let id = envelope.id.clone().unwrap_or(Value::Null);
+    let is_notification = envelope.id.is_none();
+
+    if !is_valid_envelope(&envelope) {
+        if is_notification {
+            return Box::new(future::ok(None));
+        }
+        return Box::new(future::ok(Some(rpc_error(
+            -32600,
+            "invalid request".to_string(),
+            None,
+            id,
+        ))));
+    }
+    let method = envelope.method.unwrap();
+
+    if method != method_name {
+        if is_notification {
+            return Box::new(future::ok(None));
+        }
+        return Box::new(future::ok(Some(rpc_error(
+            -32601,
+            format!("method {} not found", method),
+            None,
+            id,
+        ))));
+    }
+
+    let request: S::Request = match serde_json::from_value(envelope.params) {
+        Ok(r) => r,
+        Err(e) => {
+            if is_notification {
+                return Box::new(future::ok(None));
+            }
+            return Box::new(future::ok(Some(rpc_error(
+                -32602,
+                e.to_string(),
+                None,
+                id,
+            ))));
+        }
+    };
+
+    // Recovers a panic in the inner service so one bad handler can't take
+    // down the rest of a batch (or the hyper worker for a single call).
+    Box::new(
+        AssertUnwindSafe(future::lazy(move || service.call(request)))
+            .catch_unwind()
+            .then(move |result| {
+                if is_notification {
+                    return future::ok(None);
+                }
+                let body = match result {
+                    Ok(Ok(resp)) => match serde_json::to_value(&resp) {
+                        Ok(v) => rpc_success(v, id),
+                        Err(e) => rpc_error(-32603, e.to_string(), None, id),
+                    },
+                    Ok(Err(e)) => rpc_error(e.code(), e.message(), e.data(), id),
+                    Err(_panic) => {
+                        rpc_error(-32603, "the handler panicked".to_string(), None, id)
+                    }
+                };
+                future::ok(Some(body))
+            }),
+    )
+}
+
+fn dispatch<S>(
+    service: Arc<S>,
+    method_name: String,
+    chunk: hyper::Chunk,
+) -> Box<Future<Item = Response, Error = hyper::Error>>
+where
+    S: Service + 'static,
+    S::Request: DeserializeOwned + 'static,
+    S::Response: Serialize,
+    S::Error: ErrorLike,
+{
+    let value: Value = match serde_json::from_slice(chunk.as_ref()) {
+        Ok(v) => v,
+        Err(e) => {
+            return Box::new(future::ok(json_response(rpc_error(
+                -32700,
+                e.to_string(),
+                None,
+                Value::Null,
+            ))));
+        }
+    };
+
+    match value {
+        Value::Array(ref items) if items.is_empty() => Box::new(future::ok(json_response(
+            rpc_error(-32600, "empty batch".to_string(), None, Value::Null),
+        ))),
+        Value::Array(items) => {
+            let calls = items
+                .into_iter()
+                .map(move |item| dispatch_one(service.clone(), method_name.clone(), item));
+            Box::new(future::join_all(calls).map(|entries| {
+                let entries: Vec<Value> = entries.into_iter().filter_map(|e| e).collect();
+                if entries.is_empty() {
+                    empty_response()
+                } else {
+                    json_response(Value::Array(entries))
+                }
+            }))
+        }
+        value => Box::new(dispatch_one(service, method_name, value).map(|entry| match entry {
+            Some(body) => json_response(body),
+            None => empty_response(),
+        })),
+    }
+}
+
+impl<S> Service for JsonRpcServer<S>
+where
+    S: Service + 'static,
+    S::Request: DeserializeOwned + 'static,
+    S::Response: Serialize,
+    S::Error: ErrorLike,
+{
+    type Request = Request;
+    type Response = Response;
+    type Error = hyper::Error;
+    type Future = Box<Future<Item = Response, Error = hyper::Error>>;
+
+    fn call(&self, req: Request) -> Self::Future {
+        let service = self.inner.clone();
+        let method_name = self.method.clone();
+        Box::new(
+            req.body()
+                .concat2()
+                .and_then(move |chunk| dispatch(service, method_name, chunk)),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Deserialize, Serialize)]
+    struct Req {
+        x: i64,
+    }
+
+    #[derive(Serialize)]
+    struct Resp {
+        y: i64,
+    }
+
+    struct Echo;
+
+    impl Service for Echo {
+        type Request = Req;
+        type Response = Resp;
+        type Error = Error;
+        type Future = Box<Future<Item = Resp, Error = Error>>;
+
+        fn call(&self, req: Req) -> Self::Future {
+            Box::new(future::ok(Resp { y: req.x }))
+        }
+    }
+
+    fn dispatch_echo(value: Value) -> Option<Value> {
+        dispatch_one(Arc::new(Echo), "echo".to_string(), value)
+            .wait()
+            .unwrap()
+    }
+
+    #[test]
+    fn success_preserves_id_and_result() {
+        let entry = dispatch_echo(json!({
+            "jsonrpc": "2.0", "method": "echo", "params": {"x": 42}, "id": 1
+        })).unwrap();
+        assert_eq!(entry["id"], json!(1));
+        assert_eq!(entry["result"]["y"], json!(42));
+    }
+
+    #[test]
+    fn bad_params_preserves_id() {
+        let entry = dispatch_echo(json!({
+            "jsonrpc": "2.0", "method": "echo", "params": "not an object", "id": 7
+        })).unwrap();
+        assert_eq!(entry["id"], json!(7));
+        assert_eq!(entry["error"]["code"], json!(-32602));
+    }
+
+    #[test]
+    fn invalid_envelope_preserves_id() {
+        let entry = dispatch_echo(json!({
+            "method": "echo", "params": {"x": 1}, "id": 9
+        })).unwrap();
+        assert_eq!(entry["id"], json!(9));
+        assert_eq!(entry["error"]["code"], json!(-32600));
+    }
+
+    #[test]
+    fn unknown_method_is_not_found() {
+        let entry = dispatch_echo(json!({
+            "jsonrpc": "2.0", "method": "nope", "params": {}, "id": 3
+        })).unwrap();
+        assert_eq!(entry["error"]["code"], json!(-32601));
+    }
+
+    #[test]
+    fn notification_produces_no_entry() {
+        let entry = dispatch_echo(json!({
+            "jsonrpc": "2.0", "method": "echo", "params": {"x": 1}
+        }));
+        assert!(entry.is_none());
+    }
+
+    #[test]
+    fn notification_with_bad_params_produces_no_entry() {
+        let entry = dispatch_echo(json!({
+            "jsonrpc": "2.0", "method": "echo", "params": "bad"
+        }));
+        assert!(entry.is_none());
+    }
+
+    fn dispatch_body(body: String) -> Response {
+        dispatch(
+            Arc::new(Echo),
+            "echo".to_string(),
+            hyper::Chunk::from(body.into_bytes()),
+        ).wait()
+            .unwrap()
+    }
+
+    fn response_json(resp: Response) -> Value {
+        let bytes = resp.body().concat2().wait().unwrap();
+        serde_json::from_slice(bytes.as_ref()).unwrap()
+    }
+
+    #[test]
+    fn batch_entries_preserve_order() {
+        let body = json!([
+            {"jsonrpc": "2.0", "method": "echo", "params": {"x": 1}, "id": 1},
+            {"jsonrpc": "2.0", "method": "echo", "params": {"x": 2}, "id": 2},
+        ]).to_string();
+        let entries = response_json(dispatch_body(body));
+        let entries = entries.as_array().unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0]["id"], json!(1));
+        assert_eq!(entries[1]["id"], json!(2));
+    }
+
+    #[test]
+    fn empty_batch_is_a_single_invalid_request_error() {
+        let value = response_json(dispatch_body("[]".to_string()));
+        assert!(value.is_object());
+        assert_eq!(value["error"]["code"], json!(-32600));
+    }
+
+    #[test]
+    fn all_notification_batch_has_no_body() {
+        let body = json!([
+            {"jsonrpc": "2.0", "method": "echo", "params": {"x": 1}},
+            {"jsonrpc": "2.0", "method": "echo", "params": {"x": 2}},
+        ]).to_string();
+        let resp = dispatch_body(body);
+        assert_eq!(resp.status(), StatusCode::NoContent);
+    }
+}
@@ -2,15 +2,24 @@ extern crate futures;
 extern crate hyper;
 extern crate serde;
 #[macro_use]
+extern crate serde_derive;
+#[macro_use]
 extern crate serde_json;
+extern crate serde_urlencoded;
 #[macro_use]
 extern crate error_chain;
 
+mod hooks;
+mod jsonrpc;
+mod router;
 mod server;
 
+pub use hooks::{ErrorInspector, IgnoreErrors};
+pub use jsonrpc::JsonRpcServer;
+pub use router::JsonRouter;
 pub use server::JsonServer;
 pub use server::{Error, ErrorKind, Result};
-pub use server::{ErrorInspector, IgnoreErrors};
+pub use server::ErrorLike;
 
 #[cfg(test)]
 mod tests {
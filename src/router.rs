@@ -0,0 +1,110 @@
+use futures::{future, Future};
+use hyper;
+use hyper::server::{Request, Response, Service};
+use hyper::Method;
+
+use server::{error_to_response, Error, ErrorKind};
+
+type BoxedEndpoint = Box<
+    Service<
+        Request = Request,
+        Response = Response,
+        Error = hyper::Error,
+        Future = Box<Future<Item = Response, Error = hyper::Error>>,
+    >,
+>;
+
+/// Routes requests to endpoints registered by `(Method, path)`.
+pub struct JsonRouter {
+    routes: Vec<(Method, String, BoxedEndpoint)>,
+}
+
+impl JsonRouter {
+    pub fn new() -> Self {
+        JsonRouter { routes: Vec::new() }
+    }
+
+    pub fn register<S>(mut self, method: Method, path: &str, endpoint: S) -> Self
+    where
+        S: Service<
+                Request = Request,
+                Response = Response,
+                Error = hyper::Error,
+                Future = Box<Future<Item = Response, Error = hyper::Error>>,
+            > + 'static,
+    {
+        self.routes.push((method, path.to_string(), Box::new(endpoint)));
+        self
+    }
+}
+
+impl Service for JsonRouter {
+    type Request = Request;
+    type Response = Response;
+    type Error = hyper::Error;
+    type Future = Box<Future<Item = Response, Error = hyper::Error>>;
+
+    fn call(&self, req: Request) -> Self::Future {
+        let path = req.path().to_string();
+        let method = req.method().clone();
+        let mut path_exists = false;
+        for &(ref m, ref p, ref endpoint) in &self.routes {
+            if *p == path {
+                path_exists = true;
+                if *m == method {
+                    return endpoint.call(req);
+                }
+            }
+        }
+        let e: Error = if path_exists {
+            ErrorKind::MethodNotAllowed.into()
+        } else {
+            ErrorKind::NotFound(path).into()
+        };
+        Box::new(future::ok(error_to_response(&e)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyper::StatusCode;
+
+    struct Fixed(StatusCode);
+
+    impl Service for Fixed {
+        type Request = Request;
+        type Response = Response;
+        type Error = hyper::Error;
+        type Future = Box<Future<Item = Response, Error = hyper::Error>>;
+
+        fn call(&self, _req: Request) -> Self::Future {
+            Box::new(future::ok(Response::new().with_status(self.0)))
+        }
+    }
+
+    fn request(method: Method, path: &str) -> Request {
+        Request::new(method, path.parse().unwrap())
+    }
+
+    #[test]
+    fn dispatches_to_the_matching_route() {
+        let router = JsonRouter::new().register(Method::Get, "/ok", Fixed(StatusCode::Ok));
+        let resp = router.call(request(Method::Get, "/ok")).wait().unwrap();
+        assert_eq!(resp.status(), StatusCode::Ok);
+    }
+
+    #[test]
+    fn unknown_path_is_not_found() {
+        let router = JsonRouter::new().register(Method::Get, "/ok", Fixed(StatusCode::Ok));
+        let resp = router.call(request(Method::Get, "/nope")).wait().unwrap();
+        assert_eq!(resp.status(), StatusCode::NotFound);
+    }
+
+    #[test]
+    fn known_path_with_wrong_method_is_method_not_allowed() {
+        let router = JsonRouter::new().register(Method::Get, "/ok", Fixed(StatusCode::Ok));
+        let resp = router.call(request(Method::Post, "/ok")).wait().unwrap();
+        assert_eq!(resp.status(), StatusCode::MethodNotAllowed);
+    }
+}
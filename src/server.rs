@@ -1,4 +1,3 @@
-use futures::future::Either;
 use futures::{future, Future, Stream};
 use hyper;
 use hyper::header::{ContentLength, ContentType};
@@ -7,6 +6,9 @@ use hyper::{Method, StatusCode};
 use serde::de::DeserializeOwned;
 use serde::Serialize;
 use serde_json;
+use serde_json::Value;
+use serde_urlencoded;
+use std::panic::AssertUnwindSafe;
 use std::sync::Arc;
 
 error_chain! {
@@ -35,17 +37,83 @@ error_chain! {
 
 pub struct JsonServer<S> {
     pub inner: Arc<S>,
+    /// When set, `GET` requests are accepted and the query string is
+    /// deserialized into the request type instead of reading a body.
+    pub allow_get: bool,
 }
 
-fn error_to_response(error: Error) -> Response {
-    let (status, body) = match error.kind() {
-        &ErrorKind::NotFound(_) => (StatusCode::NotFound, format!("{}", error)),
-        &ErrorKind::BadRequest(_) => (StatusCode::BadRequest, format!("{}", error)),
-        &ErrorKind::InternalError(_) => (StatusCode::InternalServerError, format!("{}", error)),
-        _ => (StatusCode::InternalServerError, format!("{}", error)),
-    };
+impl<S> JsonServer<S> {
+    pub fn new(inner: S) -> Self {
+        JsonServer {
+            inner: Arc::new(inner),
+            allow_get: false,
+        }
+    }
+
+    pub fn allow_get(mut self, allow_get: bool) -> Self {
+        self.allow_get = allow_get;
+        self
+    }
+}
+
+/// Lets a service's `Error` describe itself as a coded error instead of
+/// being flattened into a `Display` string.
+pub trait ErrorLike {
+    fn code(&self) -> i64;
+    fn message(&self) -> String;
+    fn data(&self) -> Option<Value> {
+        None
+    }
+    fn status(&self) -> StatusCode;
+}
+
+#[cfg(feature = "easy-errors")]
+impl<T: ::std::fmt::Display> ErrorLike for T {
+    fn code(&self) -> i64 {
+        -1
+    }
+
+    fn message(&self) -> String {
+        self.to_string()
+    }
+
+    fn status(&self) -> StatusCode {
+        StatusCode::InternalServerError
+    }
+}
+
+#[cfg(not(feature = "easy-errors"))]
+impl ErrorLike for Error {
+    fn code(&self) -> i64 {
+        match self.kind() {
+            &ErrorKind::NotFound(_) => 404,
+            &ErrorKind::BadRequest(_) => 400,
+            &ErrorKind::MethodNotAllowed => 405,
+            _ => 500,
+        }
+    }
+
+    fn message(&self) -> String {
+        self.to_string()
+    }
+
+    fn status(&self) -> StatusCode {
+        match self.code() {
+            404 => StatusCode::NotFound,
+            400 => StatusCode::BadRequest,
+            405 => StatusCode::MethodNotAllowed,
+            _ => StatusCode::InternalServerError,
+        }
+    }
+}
+
+pub(crate) fn error_to_response(error: &ErrorLike) -> Response {
     let resp = json!({
-        "error": body,
+        "error": {
+            "code": error.code(),
+            "message": error.message(),
+            "data": error.data(),
+        },
     });
     let body = resp.to_string();
     let body_len = body.len() as u64;
@@ -53,7 +121,7 @@ fn error_to_response(error: Error) -> Response {
         .with_body(body)
         .with_header(ContentLength(body_len))
         .with_header(ContentType::json())
-        .with_status(status)
+        .with_status(error.status())
 }
 
 impl<S: Service + JsonService + 'static> Service for JsonServer<S> {
@@ -64,38 +132,71 @@ impl<S: Service + JsonService + 'static> Service for JsonServer<S> {
 
     fn call(&self, req: Request) -> Self::Future {
         let service = self.inner.clone();
-        Box::new(if *req.method() == Method::Post {
-            match service.deserialize(req.path(), req.method()) {
-                Ok(f) => {
-                    let req = req.body()
+        let allow_get = self.allow_get;
+        let fut = future::lazy(move || -> Box<Future<Item = Response, Error = hyper::Error>> {
+            match *req.method() {
+            Method::Post => match service.deserialize(req.path(), req.method()) {
+                Ok(f) => Box::new(
+                    req.body()
                         .concat2()
                         .map_err(move |e| ErrorKind::InternalError(e.to_string()).into())
-                        .and_then(move |chunk| f(chunk.as_ref()));
-                    let res = req.and_then(move |req| {
-                        service
-                            .call(req)
-                            .then(move |res| match service.serialize(res) {
-                                Ok(body) => {
-                                    let len = body.len() as u64;
-                                    let resp = Response::new()
-                                        .with_body(body)
-                                        .with_header(ContentLength(len))
-                                        .with_header(ContentType::json())
-                                        .with_status(StatusCode::Ok);
-                                    future::ok(resp)
-                                }
-                                Err(e) => future::ok(error_to_response(e)),
-                            })
-                    }).or_else(|e| future::ok(error_to_response(e)));
-                    Either::A(res)
-                }
-                Err(e) => Either::B(future::ok(error_to_response(e))),
+                        .and_then(move |chunk| f(chunk.as_ref()))
+                        .then(move |parsed| call_and_respond(service, parsed)),
+                ),
+                Err(e) => Box::new(future::ok(error_to_response(&e))),
+            },
+            Method::Get if allow_get => {
+                let query = req.query().unwrap_or("").to_string();
+                Box::new(future::lazy(move || {
+                    let parsed = service.deserialize_query(&query);
+                    call_and_respond(service, parsed)
+                }))
             }
-        } else {
-            Either::B(future::ok(error_to_response(
-                ErrorKind::MethodNotAllowed.into(),
-            )))
-        })
+            _ => {
+                let e: Error = ErrorKind::MethodNotAllowed.into();
+                Box::new(future::ok(error_to_response(&e)))
+            }
+            }
+        });
+        // A panic anywhere in the inner service's deserialize/call/serialize
+        // path is recovered here and turned into a normal 500 response
+        // instead of taking down the hyper worker for this connection.
+        Box::new(
+            AssertUnwindSafe(fut)
+                .catch_unwind()
+                .then(|result| match result {
+                    Ok(inner) => inner,
+                    Err(_panic) => {
+                        let e: Error =
+                            ErrorKind::InternalError("the handler panicked".to_string()).into();
+                        Ok(error_to_response(&e))
+                    }
+                }),
+        )
+    }
+}
+
+fn call_and_respond<S>(
+    service: Arc<S>,
+    parsed: Result<<S as Service>::Request>,
+) -> Box<Future<Item = Response, Error = hyper::Error>>
+where
+    S: Service + JsonService + 'static,
+{
+    match parsed {
+        Ok(req) => Box::new(service.call(req).then(move |res| match service.serialize(res) {
+            Ok(body) => {
+                let len = body.len() as u64;
+                let resp = Response::new()
+                    .with_body(body)
+                    .with_header(ContentLength(len))
+                    .with_header(ContentType::json())
+                    .with_status(StatusCode::Ok);
+                future::ok(resp)
+            }
+            Err(e) => future::ok(error_to_response(e.as_ref())),
+        })),
+        Err(e) => Box::new(future::ok(error_to_response(&e))),
     }
 }
 
@@ -108,10 +209,13 @@ where
         path: &str,
         method: &Method,
     ) -> Result<fn(&[u8]) -> Result<<Self as Service>::Request>>;
+    /// Parses a URL query string into the request type, for endpoints that
+    /// opt into `GET` via `JsonServer::allow_get`.
+    fn deserialize_query(&self, query: &str) -> Result<<Self as Service>::Request>;
     fn serialize(
         &self,
         resp: ::std::result::Result<Self::Response, <Self as Service>::Error>,
-    ) -> Result<Vec<u8>>;
+    ) -> ::std::result::Result<Vec<u8>, Box<ErrorLike>>;
 }
 
 impl<S> JsonService for S
@@ -119,7 +223,7 @@ where
     S: Service,
     <S as Service>::Request: DeserializeOwned + 'static,
     <S as Service>::Response: Serialize,
-    <S as Service>::Error: Into<Error>,
+    <S as Service>::Error: ErrorLike + 'static,
 {
     fn deserialize(
         &self,
@@ -132,13 +236,106 @@ where
         })
     }
 
+    fn deserialize_query(&self, query: &str) -> Result<<S as Service>::Request> {
+        serde_urlencoded::from_str(query).map_err(|e| ErrorKind::BadRequest(e.to_string()).into())
+    }
+
     fn serialize(
         &self,
         resp: ::std::result::Result<S::Response, <S as Service>::Error>,
-    ) -> Result<Vec<u8>> {
+    ) -> ::std::result::Result<Vec<u8>, Box<ErrorLike>> {
         match resp {
             Ok(res) => Ok(serde_json::to_vec(&res).unwrap()),
-            Err(e) => Err(e.into()),
+            Err(e) => Err(Box::new(e)),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Deserialize, Serialize)]
+    struct Req {
+        x: i64,
+    }
+
+    #[derive(Serialize)]
+    struct Resp {
+        y: i64,
+    }
+
+    struct Echo;
+
+    impl Service for Echo {
+        type Request = Req;
+        type Response = Resp;
+        type Error = Error;
+        type Future = Box<Future<Item = Resp, Error = Error>>;
+
+        fn call(&self, req: Req) -> Self::Future {
+            Box::new(future::ok(Resp { y: req.x }))
+        }
+    }
+
+    struct Panicker;
+
+    impl Service for Panicker {
+        type Request = Req;
+        type Response = Resp;
+        type Error = Error;
+        type Future = Box<Future<Item = Resp, Error = Error>>;
+
+        fn call(&self, _req: Req) -> Self::Future {
+            panic!("the handler panicked");
+        }
+    }
+
+    fn get_request(uri: &str) -> Request {
+        Request::new(Method::Get, uri.parse().unwrap())
+    }
+
+    fn post_request(body: &str) -> Request {
+        let mut req = Request::new(Method::Post, "/".parse().unwrap());
+        req.set_body(body.to_string());
+        req
+    }
+
+    fn response_json(resp: Response) -> Value {
+        let bytes = resp.body().concat2().wait().unwrap();
+        serde_json::from_slice(bytes.as_ref()).unwrap()
+    }
+
+    #[test]
+    fn bad_json_body_reports_error_like_code_and_message() {
+        let server = JsonServer::new(Echo);
+        let resp = server.call(post_request("not json")).wait().unwrap();
+        assert_eq!(resp.status(), StatusCode::BadRequest);
+        let body = response_json(resp);
+        assert_eq!(body["error"]["code"], json!(400));
+        assert!(body["error"]["message"].is_string());
+    }
+
+    #[test]
+    fn get_is_rejected_unless_allowed() {
+        let server = JsonServer::new(Echo);
+        let resp = server.call(get_request("/?x=1")).wait().unwrap();
+        assert_eq!(resp.status(), StatusCode::MethodNotAllowed);
+    }
+
+    #[test]
+    fn get_deserializes_query_when_allowed() {
+        let server = JsonServer::new(Echo).allow_get(true);
+        let resp = server.call(get_request("/?x=5")).wait().unwrap();
+        assert_eq!(resp.status(), StatusCode::Ok);
+        let body = response_json(resp);
+        assert_eq!(body["y"], json!(5));
+    }
+
+    #[test]
+    fn panicking_service_becomes_internal_error_response() {
+        let server = JsonServer::new(Panicker).allow_get(true);
+        let resp = server.call(get_request("/?x=1")).wait().unwrap();
+        assert_eq!(resp.status(), StatusCode::InternalServerError);
+    }
+}